@@ -1,20 +1,70 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use gloo_timers::callback::Timeout;
+use pulldown_cmark::{Event, Parser, Tag};
 use serde::{Deserialize, Serialize};
 use web_sys::HtmlInputElement;
+use yew::html::Scope;
 use yew::prelude::*;
 use yew_agent::{Bridge, Bridged};
 
 use crate::services::event_bus::EventBus;
+use crate::services::storage;
 use crate::{services::websocket::WebsocketService, User};
 
 pub enum Msg {
     HandleMsg(String),
     SubmitMessage,
+    EditMessage(String, String),
+    DeleteMessage(String),
+    InputChanged,
+    StopTyping,
+    OpenChannel(Channel),
+    /// The socket reported a close or error; start (re)connecting.
+    Disconnected,
+    /// A scheduled reconnect attempt is due.
+    Reconnect,
+    /// The socket opened (or re-opened) successfully.
+    Connected,
+    /// The current connection has stayed up long enough to reset the backoff.
+    ConnectionStable,
+    /// Wipe the locally persisted conversation.
+    ClearHistory,
+}
+
+/// Health of the underlying socket, surfaced in the header bar.
+#[derive(Clone, PartialEq)]
+pub enum ConnectionState {
+    Connecting,
+    Open,
+    Reconnecting,
+}
+
+/// Which conversation the message pane is currently showing.
+#[derive(Clone, PartialEq)]
+pub enum Channel {
+    /// The shared broadcast room.
+    Public,
+    /// A one-to-one thread with the named user.
+    Direct(String),
 }
 
-#[derive(Deserialize)]
+#[derive(Clone, Deserialize, Serialize)]
 struct MessageData {
     from: String,
     message: String,
+    /// Client-generated id, unique for the lifetime of the sending session, so
+    /// later `Edit`/`Delete` verbs can address this exact message.
+    #[serde(default)]
+    id: String,
+    /// Set once the body has been replaced by an `Edit`, so the view can show
+    /// an "(edited)" marker.
+    #[serde(default)]
+    edited: bool,
+    /// Recipient for a whisper; `None` means the message was broadcast to the
+    /// public room.
+    #[serde(default)]
+    to: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -23,6 +73,17 @@ pub enum MsgTypes {
     Users,
     Register,
     Message,
+    Edit,
+    Delete,
+    Typing,
+}
+
+/// Payload carried by [`MsgTypes::Typing`]: who is typing, and whether they
+/// just started (`true`) or stopped (`false`).
+#[derive(Deserialize, Serialize)]
+struct TypingData {
+    from: String,
+    typing: bool,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -31,6 +92,13 @@ struct WebSocketMessage {
     message_type: MsgTypes,
     data_array: Option<Vec<String>>,
     data: Option<String>,
+    /// Target message id for the `Edit`/`Delete` verbs; `None` for everything
+    /// else.
+    #[serde(default)]
+    id: Option<String>,
+    /// Recipient for a whisper; `None` routes to the public room.
+    #[serde(default)]
+    to: Option<String>,
 }
 
 #[derive(Clone)]
@@ -45,39 +113,348 @@ pub struct Chat {
     _producer: Box<dyn Bridge<EventBus>>,
     wss: WebsocketService,
     messages: Vec<MessageData>,
+    username: String,
+    /// Monotonic counter feeding [`Chat::next_message_id`].
+    msg_counter: u32,
+    /// Users for whom we've seen a "typing" event but not yet a "stopped" one.
+    typing_users: HashSet<String>,
+    /// Epoch-ms of the last "typing" signal we emitted, used to debounce.
+    last_typing_at: f64,
+    /// Pending "stopped typing" fire; dropping it cancels the scheduled event.
+    typing_timeout: Option<Timeout>,
+    /// The conversation currently shown in the message pane.
+    active_channel: Channel,
+    /// Per-user count of direct messages received while viewing another
+    /// channel, cleared when that thread is opened.
+    unread: HashMap<String, usize>,
+    /// Current socket health, surfaced in the header.
+    connection_state: ConnectionState,
+    /// Messages that failed to send while offline, replayed on reconnect.
+    outbox: VecDeque<String>,
+    /// Next reconnect delay (ms); doubles per failed attempt up to the cap.
+    backoff_ms: u32,
+    /// Pending reconnect fire; dropping it cancels the scheduled attempt.
+    reconnect_timeout: Option<Timeout>,
+    /// Fires once a fresh connection has stayed up long enough to reset backoff.
+    stable_timeout: Option<Timeout>,
 }
-impl Component for Chat {
-    type Message = Msg;
-    type Properties = ();
 
-    fn create(ctx: &Context<Self>) -> Self {
-        let (user, _) = ctx
-            .link()
-            .context::<User>(Callback::noop())
-            .expect("context to be set");
-        let wss = WebsocketService::new();
-        let username = user.username.borrow().clone();
+/// Only emit one "typing" signal per this many milliseconds of activity.
+const TYPING_THROTTLE_MS: f64 = 2000.0;
+/// Fire "stopped typing" this long after the last keystroke.
+const TYPING_STOP_DELAY_MS: u32 = 3000;
+/// First reconnect delay; doubles on every failed attempt.
+const INITIAL_BACKOFF_MS: u32 = 500;
+/// Upper bound the backoff doubling saturates at.
+const MAX_BACKOFF_MS: u32 = 30000;
+/// A connection that stays open this long is considered healthy, resetting the
+/// backoff to its initial value.
+const BACKOFF_RESET_MS: u32 = 5000;
+/// Hard cap on queued-while-offline messages so a long outage can't grow the
+/// outbox without bound.
+const OUTBOX_CAP: usize = 100;
+/// Local-storage key prefix for a single channel's persisted messages.
+const HISTORY_PREFIX: &str = "yewchat.messages.";
+/// Local-storage key holding the list of channel ids we've persisted.
+const CHANNEL_INDEX_KEY: &str = "yewchat.channels";
+/// Local-storage key holding the last-seen user roster.
+const USERS_KEY: &str = "yewchat.users";
+/// Most recent messages retained per channel; older ones are evicted.
+const RETENTION_CAP: usize = 200;
+/// Returns `true` for link targets we are willing to render as clickable
+/// anchors. Anything that isn't a plain `http`/`https` URL (notably
+/// `javascript:` payloads) is rejected so a message can never smuggle in an
+/// active scheme.
+fn is_safe_link(url: &str) -> bool {
+    let lowered = url.trim().to_ascii_lowercase();
+    lowered.starts_with("http://") || lowered.starts_with("https://")
+}
+
+impl Chat {
+    /// Returns a fresh id, unique within this session, for an outgoing message.
+    /// Scoping it with the username keeps ids distinct across participants even
+    /// though each client counts from zero.
+    fn next_message_id(&mut self) -> String {
+        self.msg_counter += 1;
+        format!("{}-{}", self.username, self.msg_counter)
+    }
+
+    /// Returns `true` if `m` belongs in the given `channel` from this user's
+    /// point of view.
+    fn message_in_channel(&self, m: &MessageData, channel: &Channel) -> bool {
+        match channel {
+            Channel::Public => m.to.is_none(),
+            Channel::Direct(other) => match &m.to {
+                Some(to) => {
+                    (m.from == *other && *to == self.username)
+                        || (m.from == self.username && *to == *other)
+                }
+                None => false,
+            },
+        }
+    }
+
+    /// Opens a socket wired to dispatch [`Msg::Connected`] once the socket has
+    /// been created (the handshake may still be in progress) and
+    /// [`Msg::Disconnected`] on close/error.
+    fn open_socket(link: &Scope<Self>) -> WebsocketService {
+        let on_open = link.callback(|_| Msg::Connected);
+        let on_close = link.callback(|_| Msg::Disconnected);
+        WebsocketService::new(on_open, on_close)
+    }
+
+    /// Stable storage id for the channel a message belongs to, from this user's
+    /// perspective: `"public"` for broadcasts, or a canonical `"dm:a|b"` key
+    /// (participants sorted) for whispers.
+    fn channel_id(&self, m: &MessageData) -> String {
+        match &m.to {
+            None => "public".to_string(),
+            Some(to) => {
+                let mut pair = [m.from.clone(), to.clone()];
+                pair.sort();
+                format!("dm:{}|{}", pair[0], pair[1])
+            }
+        }
+    }
+
+    /// Rebuilds a [`UserProfile`] from a bare username, mirroring the avatar
+    /// convention used when the roster arrives over the socket.
+    fn profile_for(name: &str) -> UserProfile {
+        UserProfile {
+            name: name.to_string(),
+            avatar: format!(
+                "https://avatars.dicebear.com/api/adventurer-neutral/{}.svg",
+                name
+            ),
+        }
+    }
+
+    /// Reads the persisted roster and every known channel's history back into a
+    /// flat message list, so a reload shows the conversation before the socket
+    /// even opens.
+    fn hydrate() -> (Vec<UserProfile>, Vec<MessageData>) {
+        let users = storage::load_vec::<String>(USERS_KEY)
+            .iter()
+            .map(|n| Self::profile_for(n))
+            .collect();
+        let mut messages = Vec::new();
+        for id in storage::load_vec::<String>(CHANNEL_INDEX_KEY) {
+            messages.extend(storage::load_vec::<MessageData>(&format!(
+                "{}{}",
+                HISTORY_PREFIX, id
+            )));
+        }
+        (users, messages)
+    }
 
+    /// Persists the current roster and messages, bucketed per channel and capped
+    /// at [`RETENTION_CAP`] most-recent entries each. Called whenever either
+    /// changes so storage always tracks component state.
+    fn persist(&self) {
+        let names: Vec<String> = self.users.iter().map(|u| u.name.clone()).collect();
+        storage::save_vec(USERS_KEY, &names);
+
+        let mut buckets: HashMap<String, Vec<MessageData>> = HashMap::new();
+        for m in &self.messages {
+            buckets.entry(self.channel_id(m)).or_default().push(m.clone());
+        }
+        for (id, mut msgs) in buckets.iter().map(|(k, v)| (k.clone(), v.clone())) {
+            if msgs.len() > RETENTION_CAP {
+                msgs.drain(0..msgs.len() - RETENTION_CAP);
+            }
+            storage::save_vec(&format!("{}{}", HISTORY_PREFIX, id), &msgs);
+        }
+        let index: Vec<String> = buckets.keys().cloned().collect();
+        storage::save_vec(CHANNEL_INDEX_KEY, &index);
+    }
+
+    /// Drops every persisted channel along with the index and roster.
+    fn clear_history(&self) {
+        for id in storage::load_vec::<String>(CHANNEL_INDEX_KEY) {
+            storage::delete(&format!("{}{}", HISTORY_PREFIX, id));
+        }
+        storage::delete(CHANNEL_INDEX_KEY);
+        storage::delete(USERS_KEY);
+    }
+
+    /// Serialized `Register` frame for the current user, re-sent on every
+    /// (re)connect so the server re-adds us to its roster.
+    fn register_payload(&self) -> String {
         let message = WebSocketMessage {
             message_type: MsgTypes::Register,
-            data: Some(username.to_string()),
+            data: Some(self.username.clone()),
             data_array: None,
+            id: None,
+            to: None,
         };
+        serde_json::to_string(&message).unwrap()
+    }
 
-        if let Ok(_) = wss
+    /// Sends a serialized frame, or parks it in the bounded outbox for replay if
+    /// the connection isn't open or the socket rejects it. The oldest frame is
+    /// evicted once the outbox is full.
+    fn send_or_queue(&mut self, serialized: String) {
+        let offline = self.connection_state != ConnectionState::Open;
+        if offline
+            || self
+                .wss
+                .tx
+                .clone()
+                .try_send(serialized.clone())
+                .is_err()
+        {
+            if self.outbox.len() >= OUTBOX_CAP {
+                self.outbox.pop_front();
+            }
+            self.outbox.push_back(serialized);
+        }
+    }
+
+    /// Computes the next backoff delay with a small random jitter and doubles
+    /// the stored value for the attempt after this one, saturating at the cap.
+    fn next_backoff(&mut self) -> u32 {
+        let base = self.backoff_ms;
+        let jitter = (js_sys::Math::random() * (base as f64) * 0.25) as u32;
+        self.backoff_ms = (base.saturating_mul(2)).min(MAX_BACKOFF_MS);
+        base + jitter
+    }
+
+    /// Emits a typing presence event for the current user over the socket.
+    fn send_typing(&self, typing: bool) {
+        let payload = TypingData {
+            from: self.username.clone(),
+            typing,
+        };
+        let message = WebSocketMessage {
+            message_type: MsgTypes::Typing,
+            data: Some(serde_json::to_string(&payload).unwrap()),
+            data_array: None,
+            id: None,
+            to: None,
+        };
+        if let Err(e) = self
+            .wss
             .tx
             .clone()
             .try_send(serde_json::to_string(&message).unwrap())
         {
-            log::debug!("message sent successfully");
+            log::debug!("error sending to channel: {:?}", e);
         }
+    }
+
+    /// Renders a message body as rich text.
+    ///
+    /// The raw string is parsed as CommonMark and the resulting event stream is
+    /// mapped straight onto Yew virtual nodes — we never build an HTML string or
+    /// touch `dangerously_set_inner_html`, so untrusted input can only ever
+    /// become text or one of the handful of tags we opt into below. Bare image
+    /// URLs keep the original `.gif` inline-image behavior as a fallback.
+    fn render_message_body(&self, raw: &str) -> Html {
+        if raw.ends_with(".gif") && is_safe_link(raw) {
+            return html! { <img class="mt-2" src={raw.to_string()}/> };
+        }
+
+        // A stack of partially-built nodes: each frame collects the children of
+        // one open tag, and closing the tag folds the frame into its parent.
+        let mut stack: Vec<Vec<Html>> = vec![Vec::new()];
+        let mut link_dest: Vec<String> = Vec::new();
+
+        for event in Parser::new(raw) {
+            match event {
+                Event::Start(tag) => {
+                    if let Tag::Link(_, dest, _) = &tag {
+                        link_dest.push(dest.to_string());
+                    }
+                    stack.push(Vec::new());
+                }
+                Event::End(tag) => {
+                    let children = stack.pop().unwrap_or_default();
+                    let node = match tag {
+                        Tag::Strong => html! { <strong>{ children }</strong> },
+                        Tag::Emphasis => html! { <em>{ children }</em> },
+                        Tag::CodeBlock(_) => {
+                            html! { <pre><code>{ children }</code></pre> }
+                        }
+                        Tag::List(_) => html! { <ul class="list-disc ml-5">{ children }</ul> },
+                        Tag::Item => html! { <li>{ children }</li> },
+                        Tag::Link(_, _, _) => {
+                            let dest = link_dest.pop().unwrap_or_default();
+                            if is_safe_link(&dest) {
+                                html! {
+                                    <a href={dest} target="_blank" rel="noopener"
+                                        class="text-indigo-600 underline">
+                                        { children }
+                                    </a>
+                                }
+                            } else {
+                                // Unsafe scheme: degrade to the link text only.
+                                html! { <>{ children }</> }
+                            }
+                        }
+                        Tag::Paragraph => html! { <p>{ children }</p> },
+                        // Unknown/unsupported containers collapse to their text.
+                        _ => html! { <>{ children }</> },
+                    };
+                    stack.last_mut().unwrap().push(node);
+                }
+                Event::Text(text) => {
+                    stack.last_mut().unwrap().push(html! { { text.to_string() } });
+                }
+                Event::Code(code) => {
+                    stack
+                        .last_mut()
+                        .unwrap()
+                        .push(html! { <code>{ code.to_string() }</code> });
+                }
+                Event::SoftBreak | Event::HardBreak => {
+                    stack.last_mut().unwrap().push(html! { <br/> });
+                }
+                // Everything else (html, rules, footnotes…) degrades to nothing.
+                _ => {}
+            }
+        }
+
+        html! { <>{ stack.pop().unwrap_or_default() }</> }
+    }
+}
+
+impl Component for Chat {
+    type Message = Msg;
+    type Properties = ();
+
+    fn create(ctx: &Context<Self>) -> Self {
+        let (user, _) = ctx
+            .link()
+            .context::<User>(Callback::noop())
+            .expect("context to be set");
+        // The socket reports its lifecycle back to us: `onopen` re-registers and
+        // flushes the outbox, `onclose`/`onerror` kick off reconnection.
+        let wss = Self::open_socket(ctx.link());
+        let username = user.username.borrow().clone();
+
+        // Hydrate from local storage before the first render so a reload shows
+        // the prior conversation immediately; the server reconciles the roster
+        // once the socket opens.
+        let (users, messages) = Self::hydrate();
 
         Self {
-            users: vec![],
-            messages: vec![],
+            users,
+            messages,
             chat_input: NodeRef::default(),
             wss,
             _producer: EventBus::bridge(ctx.link().callback(Msg::HandleMsg)),
+            username,
+            msg_counter: 0,
+            typing_users: HashSet::new(),
+            last_typing_at: 0.0,
+            typing_timeout: None,
+            active_channel: Channel::Public,
+            unread: HashMap::new(),
+            connection_state: ConnectionState::Connecting,
+            outbox: VecDeque::new(),
+            backoff_ms: INITIAL_BACKOFF_MS,
+            reconnect_timeout: None,
+            stable_timeout: None,
         }
     }
 
@@ -90,21 +467,60 @@ impl Component for Chat {
                         let users_from_message = msg.data_array.unwrap_or_default();
                         self.users = users_from_message
                             .iter()
-                            .map(|u| UserProfile {
-                                name: u.into(),
-                                avatar: format!(
-                                    "https://avatars.dicebear.com/api/adventurer-neutral/{}.svg",
-                                    u
-                                )
-                                .into(),
-                            })
+                            .map(|u| Self::profile_for(u))
                             .collect();
+                        self.persist();
                         return true;
                     }
                     MsgTypes::Message => {
                         let message_data: MessageData =
                             serde_json::from_str(&msg.data.unwrap()).unwrap();
+                        // An incoming whisper addressed to us bumps the unread
+                        // badge for the sender unless we're already reading it.
+                        if let Some(to) = &message_data.to {
+                            if *to == self.username
+                                && self.active_channel
+                                    != Channel::Direct(message_data.from.clone())
+                            {
+                                *self.unread.entry(message_data.from.clone()).or_insert(0) += 1;
+                            }
+                        }
                         self.messages.push(message_data);
+                        self.persist();
+                        return true;
+                    }
+                    MsgTypes::Typing => {
+                        let typing: TypingData =
+                            serde_json::from_str(&msg.data.unwrap()).unwrap();
+                        if typing.typing {
+                            self.typing_users.insert(typing.from);
+                        } else {
+                            self.typing_users.remove(&typing.from);
+                        }
+                        return true;
+                    }
+                    MsgTypes::Edit => {
+                        if let Some(id) = msg.id {
+                            if let Some(existing) =
+                                self.messages.iter_mut().find(|m| m.id == id)
+                            {
+                                existing.message = msg.data.unwrap_or_default();
+                                existing.edited = true;
+                            }
+                        }
+                        self.persist();
+                        return true;
+                    }
+                    MsgTypes::Delete => {
+                        if let Some(id) = msg.id {
+                            if let Some(existing) =
+                                self.messages.iter_mut().find(|m| m.id == id)
+                            {
+                                existing.message = "message deleted".to_string();
+                                existing.edited = false;
+                            }
+                        }
+                        self.persist();
                         return true;
                     }
                     _ => {
@@ -115,28 +531,129 @@ impl Component for Chat {
             Msg::SubmitMessage => {
                 let input = self.chat_input.cast::<HtmlInputElement>();
                 if let Some(input) = input {
+                    let to = match &self.active_channel {
+                        Channel::Public => None,
+                        Channel::Direct(name) => Some(name.clone()),
+                    };
                     let message = WebSocketMessage {
                         message_type: MsgTypes::Message,
                         data: Some(input.value()),
                         data_array: None,
+                        id: Some(self.next_message_id()),
+                        to,
                     };
-                    if let Err(e) = self
-                        .wss
-                        .tx
-                        .clone()
-                        .try_send(serde_json::to_string(&message).unwrap())
-                    {
-                        log::debug!("error sending to channel: {:?}", e);
-                    }
+                    self.send_or_queue(serde_json::to_string(&message).unwrap());
                     input.set_value("");
                 };
                 false
             }
+            Msg::InputChanged => {
+                // Throttle the "typing" signal to at most one per window, but
+                // always (re)arm the "stopped typing" timeout so it fires once
+                // the user falls quiet even if they never send.
+                let now = js_sys::Date::now();
+                if now - self.last_typing_at > TYPING_THROTTLE_MS {
+                    self.send_typing(true);
+                    self.last_typing_at = now;
+                }
+                let link = _ctx.link().clone();
+                self.typing_timeout = Some(Timeout::new(TYPING_STOP_DELAY_MS, move || {
+                    link.send_message(Msg::StopTyping);
+                }));
+                false
+            }
+            Msg::StopTyping => {
+                self.typing_timeout = None;
+                self.last_typing_at = 0.0;
+                self.send_typing(false);
+                false
+            }
+            Msg::OpenChannel(channel) => {
+                if let Channel::Direct(name) = &channel {
+                    self.unread.remove(name);
+                }
+                self.active_channel = channel;
+                true
+            }
+            Msg::EditMessage(id, new_text) => {
+                let message = WebSocketMessage {
+                    message_type: MsgTypes::Edit,
+                    data: Some(new_text),
+                    data_array: None,
+                    id: Some(id),
+                    to: None,
+                };
+                self.send_or_queue(serde_json::to_string(&message).unwrap());
+                false
+            }
+            Msg::DeleteMessage(id) => {
+                let message = WebSocketMessage {
+                    message_type: MsgTypes::Delete,
+                    data: None,
+                    data_array: None,
+                    id: Some(id),
+                    to: None,
+                };
+                self.send_or_queue(serde_json::to_string(&message).unwrap());
+                false
+            }
+            Msg::Disconnected => {
+                // Already trying to come back? Leave the scheduled attempt be.
+                if self.reconnect_timeout.is_some() {
+                    return false;
+                }
+                self.connection_state = ConnectionState::Reconnecting;
+                self.stable_timeout = None;
+                let delay = self.next_backoff();
+                log::debug!("socket down, reconnecting in {}ms", delay);
+                let link = _ctx.link().clone();
+                self.reconnect_timeout = Some(Timeout::new(delay, move || {
+                    link.send_message(Msg::Reconnect);
+                }));
+                true
+            }
+            Msg::Reconnect => {
+                self.reconnect_timeout = None;
+                // A fresh socket; its `onopen` callback drives Msg::Connected.
+                self.wss = Self::open_socket(_ctx.link());
+                false
+            }
+            Msg::Connected => {
+                self.connection_state = ConnectionState::Open;
+                // Re-register so the server re-adds us, then replay anything that
+                // piled up in the outbox while we were offline.
+                self.send_or_queue(self.register_payload());
+                while let Some(frame) = self.outbox.pop_front() {
+                    if self.wss.tx.clone().try_send(frame.clone()).is_err() {
+                        // Still not writable; put it back and stop flushing.
+                        self.outbox.push_front(frame);
+                        break;
+                    }
+                }
+                // Reset the backoff only once the link proves stable.
+                let link = _ctx.link().clone();
+                self.stable_timeout = Some(Timeout::new(BACKOFF_RESET_MS, move || {
+                    link.send_message(Msg::ConnectionStable);
+                }));
+                true
+            }
+            Msg::ConnectionStable => {
+                self.stable_timeout = None;
+                self.backoff_ms = INITIAL_BACKOFF_MS;
+                false
+            }
+            Msg::ClearHistory => {
+                self.clear_history();
+                self.messages.clear();
+                self.unread.clear();
+                true
+            }
         }
     }
 
     fn view(&self, ctx: &Context<Self>) -> Html {
         let submit = ctx.link().callback(|_| Msg::SubmitMessage);
+        let oninput = ctx.link().callback(|_: InputEvent| Msg::InputChanged);
 
         html! {
             <div class="flex w-screen bg-gradient-to-r from-indigo-500 via-purple-500 to-pink-500">
@@ -145,14 +662,32 @@ impl Component for Chat {
                     <div class="overflow-y-auto">
                         {
                             self.users.clone().iter().map(|u| {
+                                let name = u.name.clone();
+                                let open = ctx
+                                    .link()
+                                    .callback(move |_: MouseEvent| Msg::OpenChannel(Channel::Direct(name.clone())));
+                                let unread = self.unread.get(&u.name).copied().unwrap_or(0);
                                 html!{
-                                    <div class="flex items-center m-4 p-3 bg-white shadow-md rounded-lg hover:bg-gray-100 transition duration-300">
-                                        <div>
+                                    <div onclick={open} class="flex items-center m-4 p-3 bg-white shadow-md rounded-lg hover:bg-gray-100 transition duration-300 cursor-pointer">
+                                        <div class="relative">
                                             <img class="w-14 h-14 rounded-full border-2 border-indigo-500" src={u.avatar.clone()} alt="avatar"/>
+                                            {
+                                                if unread > 0 {
+                                                    html!{<span class="absolute -top-1 -right-1 bg-pink-500 text-white text-xs rounded-full px-2 py-0.5">{unread}</span>}
+                                                } else {
+                                                    html!{}
+                                                }
+                                            }
                                         </div>
                                         <div class="flex-grow p-3">
                                             <div class="font-semibold text-gray-700">{u.name.clone()}</div>
-                                            <div class="text-sm text-gray-400">{"Hey, I'm here!"}</div>
+                                            {
+                                                if self.typing_users.contains(&u.name) {
+                                                    html!{<div class="text-sm text-indigo-400 italic">{"typing…"}</div>}
+                                                } else {
+                                                    html!{<div class="text-sm text-gray-400">{"Hey, I'm here!"}</div>}
+                                                }
+                                            }
                                         </div>
                                     </div>
                                 }
@@ -161,35 +696,123 @@ impl Component for Chat {
                     </div>
                 </div>
                 <div class="grow h-screen flex flex-col bg-gray-50 rounded-r-lg shadow-xl">
-                    <div class="w-full h-14 bg-indigo-600 text-white flex items-center justify-center rounded-t-lg">
-                        <div class="text-2xl font-bold">{"💬 Chat Room"}</div>
+                    <div class="w-full h-14 bg-indigo-600 text-white flex items-center justify-center relative rounded-t-lg">
+                        {
+                            match &self.active_channel {
+                                Channel::Public => html!{<div class="text-2xl font-bold">{"💬 Chat Room"}</div>},
+                                Channel::Direct(name) => {
+                                    let to_public = ctx.link().callback(|_: MouseEvent| Msg::OpenChannel(Channel::Public));
+                                    html!{
+                                        <div class="flex items-center space-x-3">
+                                            <button onclick={to_public} class="text-sm bg-indigo-500 rounded-full px-3 py-1 hover:bg-indigo-400">{"← Room"}</button>
+                                            <div class="text-2xl font-bold">{ format!("🔒 {}", name) }</div>
+                                        </div>
+                                    }
+                                }
+                            }
+                        }
+                        {
+                            let (label, color) = match self.connection_state {
+                                ConnectionState::Open => ("online", "bg-green-500"),
+                                ConnectionState::Connecting => ("connecting…", "bg-yellow-500"),
+                                ConnectionState::Reconnecting => ("offline — reconnecting…", "bg-red-500"),
+                            };
+                            let clear = ctx.link().callback(|_: MouseEvent| Msg::ClearHistory);
+                            html!{
+                                <div class="absolute right-4 flex items-center space-x-3 text-sm">
+                                    <div class="flex items-center space-x-2">
+                                        <span class={classes!("w-2", "h-2", "rounded-full", color)}></span>
+                                        <span>{label}</span>
+                                    </div>
+                                    <button onclick={clear} class="bg-indigo-500 rounded-full px-3 py-1 hover:bg-indigo-400">{"Clear history"}</button>
+                                </div>
+                            }
+                        }
                     </div>
                     <div class="w-full grow overflow-auto p-4">
                         {
-                            self.messages.iter().map(|m| {
-                                let user = self.users.iter().find(|u| u.name == m.from).unwrap();
+                            self.messages.iter().filter(|m| self.message_in_channel(m, &self.active_channel)).map(|m| {
+                                // The author may have left the roster (or been
+                                // hydrated from storage before it arrived); fall
+                                // back to a synthesized profile rather than panic.
+                                let user = self
+                                    .users
+                                    .iter()
+                                    .find(|u| u.name == m.from)
+                                    .cloned()
+                                    .unwrap_or_else(|| Self::profile_for(&m.from));
+                                let is_mine = m.from == self.username;
+                                let controls = if is_mine {
+                                    let id = m.id.clone();
+                                    let current = m.message.clone();
+                                    let edit_id = id.clone();
+                                    let on_edit = ctx.link().callback(move |_: MouseEvent| {
+                                        let next = web_sys::window()
+                                            .and_then(|w| {
+                                                w.prompt_with_message_and_default(
+                                                    "Edit message",
+                                                    &current,
+                                                )
+                                                .ok()
+                                                .flatten()
+                                            })
+                                            .unwrap_or_else(|| current.clone());
+                                        Msg::EditMessage(edit_id.clone(), next)
+                                    });
+                                    let del_id = id.clone();
+                                    let on_delete = ctx
+                                        .link()
+                                        .callback(move |_: MouseEvent| Msg::DeleteMessage(del_id.clone()));
+                                    html! {
+                                        <div class="hidden group-hover:flex space-x-2 text-xs text-gray-400 mt-1">
+                                            <button onclick={on_edit} class="hover:text-indigo-600">{"edit"}</button>
+                                            <button onclick={on_delete} class="hover:text-pink-600">{"delete"}</button>
+                                        </div>
+                                    }
+                                } else {
+                                    html!{}
+                                };
                                 html!{
-                                    <div class="flex items-end justify-start space-x-4 mb-6">
+                                    <div class="group flex items-end justify-start space-x-4 mb-6">
                                         <img class="w-10 h-10 rounded-full border-2 border-indigo-600" src={user.avatar.clone()} alt="avatar"/>
                                         <div class="max-w-xs p-3 bg-white rounded-xl shadow-md">
                                             <div class="text-sm text-indigo-600 font-semibold">{m.from.clone()}</div>
                                             <div class="text-sm text-gray-600">
+                                                { self.render_message_body(&m.message) }
                                                 {
-                                                    if m.message.ends_with(".gif") {
-                                                        html!{<img class="mt-2" src={m.message.clone()}/>}
+                                                    if m.edited {
+                                                        html!{<span class="ml-1 text-xs text-gray-400 italic">{"(edited)"}</span>}
                                                     } else {
-                                                        html!{m.message.clone()}
+                                                        html!{}
                                                     }
                                                 }
                                             </div>
+                                            { controls }
                                         </div>
                                     </div>
                                 }
                             }).collect::<Html>()
                         }
                     </div>
+                    {
+                        let others: Vec<String> = self
+                            .typing_users
+                            .iter()
+                            .filter(|u| **u != self.username)
+                            .cloned()
+                            .collect();
+                        if others.is_empty() {
+                            html!{}
+                        } else {
+                            html!{
+                                <div class="px-4 py-1 text-sm text-gray-500 italic animate-pulse">
+                                    { format!("{} is typing…", others.join(", ")) }
+                                </div>
+                            }
+                        }
+                    }
                     <div class="w-full h-16 flex items-center px-4 bg-white border-t-2 border-gray-200">
-                        <input ref={self.chat_input.clone()} type="text" placeholder="Type your message..." class="block w-full py-2 pl-4 rounded-full bg-gray-200 outline-none text-gray-700 hover:bg-gray-300 transition duration-200"/>
+                        <input ref={self.chat_input.clone()} type="text" oninput={oninput} placeholder="Type your message..." class="block w-full py-2 pl-4 rounded-full bg-gray-200 outline-none text-gray-700 hover:bg-gray-300 transition duration-200"/>
                         <button onclick={submit} class="ml-3 p-3 bg-indigo-600 text-white rounded-full hover:bg-indigo-700 transition duration-300">
                             <img src="https://img.icons8.com/ios-filled/50/ffffff/send.png" alt="send-icon" class="w-6 h-6"/>
                         </button>
@@ -0,0 +1,26 @@
+//! Thin, typed wrapper over `gloo-storage`'s `LocalStorage` used to persist the
+//! chat history across page reloads. Everything is stored as JSON under a small
+//! set of string keys so a failed read (corrupt or absent entry) degrades to an
+//! empty result rather than an error.
+
+use gloo_storage::{LocalStorage, Storage};
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Loads a serialized `Vec` from local storage, or an empty one if the key is
+/// missing or can't be decoded.
+pub fn load_vec<T: DeserializeOwned>(key: &str) -> Vec<T> {
+    LocalStorage::get(key).unwrap_or_default()
+}
+
+/// Persists `items` under `key`, silently dropping the write on quota/serialize
+/// errors — history is best-effort and must never break the live UI.
+pub fn save_vec<T: Serialize>(key: &str, items: &[T]) {
+    if let Err(e) = LocalStorage::set(key, items) {
+        log::debug!("failed to persist {}: {:?}", key, e);
+    }
+}
+
+/// Removes a stored key.
+pub fn delete(key: &str) {
+    LocalStorage::delete(key);
+}
@@ -0,0 +1,70 @@
+use futures::{channel::mpsc::Sender, SinkExt, StreamExt};
+use gloo_net::websocket::{futures::WebSocket, Message};
+use wasm_bindgen_futures::spawn_local;
+use yew::Callback;
+use yew_agent::Dispatched;
+
+use super::event_bus::{EventBus, Request};
+
+/// Wraps a single WebSocket connection: inbound text frames are forwarded onto
+/// the [`EventBus`], outbound frames are written via [`WebsocketService::tx`].
+///
+/// The service notifies its consumer about the connection lifecycle through the
+/// `on_open`/`on_close` callbacks so the component can drive reconnection and
+/// replay queued messages.
+pub struct WebsocketService {
+    pub tx: Sender<String>,
+}
+
+impl WebsocketService {
+    pub fn new(on_open: Callback<()>, on_close: Callback<()>) -> Self {
+        let ws = match WebSocket::open("ws://127.0.0.1:8080") {
+            Ok(ws) => ws,
+            Err(e) => {
+                // Couldn't even start dialing — report it as a closed socket so
+                // the consumer schedules a retry.
+                log::debug!("failed to open websocket: {:?}", e);
+                on_close.emit(());
+                let (tx, _) = futures::channel::mpsc::channel::<String>(1000);
+                return Self { tx };
+            }
+        };
+
+        let (mut write, mut read) = ws.split();
+        let (in_tx, mut in_rx) = futures::channel::mpsc::channel::<String>(1000);
+
+        // `WebSocket::open` returns before the handshake completes, so this fires
+        // while the socket may still be connecting; let the consumer
+        // (re-)register and flush.
+        on_open.emit(());
+
+        let write_on_close = on_close.clone();
+        spawn_local(async move {
+            while let Some(s) = in_rx.next().await {
+                if let Err(e) = write.send(Message::Text(s)).await {
+                    log::debug!("websocket write failed: {:?}", e);
+                    write_on_close.emit(());
+                    break;
+                }
+            }
+        });
+
+        let mut event_bus = EventBus::dispatcher();
+        spawn_local(async move {
+            while let Some(msg) = read.next().await {
+                match msg {
+                    Ok(Message::Text(data)) => event_bus.send(Request::EventBusMsg(data)),
+                    Ok(Message::Bytes(_)) => {}
+                    Err(e) => {
+                        log::debug!("websocket read error: {:?}", e);
+                        break;
+                    }
+                }
+            }
+            // The read stream ended: the socket is closed.
+            on_close.emit(());
+        });
+
+        Self { tx: in_tx }
+    }
+}